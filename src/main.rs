@@ -3,19 +3,36 @@ use bevy::{
 };
 use rand::{
     distributions::{Distribution, Standard},
+    seq::SliceRandom,
     Rng,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 fn main() {
     App::build()
         .add_default_plugins()
         .add_resource(SoftDropTimer(Timer::from_seconds(0.750)))
         .add_resource(PrintInfoTimer(Timer::from_seconds(1.0)))
+        .add_resource(Score(0))
+        .add_resource(Level(1))
+        .add_resource(LinesCleared(0))
+        .add_resource(PieceBag(VecDeque::new()))
+        .add_resource(AlreadyHeld(false))
+        .add_resource(LockTimer {
+            timer: Timer::from_seconds(0.5),
+            active: false,
+            resets: 0,
+        })
+        .add_resource(GameOver(false))
         .add_startup_system(setup.system())
         // .add_system(print_info.system())
         .add_system(move_current_tetromino.system())
+        .add_system(hold_current_tetromino.system())
+        .add_system(update_ghost_tetromino.system())
+        .add_system(restart_game.system())
         .add_system(update_block_sprites.system())
+        .add_system(update_score_ui.system())
+        .add_system(update_game_over_ui.system())
         .run();
 }
 
@@ -23,6 +40,33 @@ struct SoftDropTimer(Timer);
 
 struct PrintInfoTimer(Timer);
 
+// Tracks the lock-delay window for the currently resting piece.
+struct LockTimer {
+    timer: Timer,
+    active: bool,
+    resets: u32,
+}
+
+// Caps lock-delay resets so a piece can't be stalled on the heap forever.
+const LOCK_RESET_LIMIT: u32 = 15;
+
+// Total points earned this game.
+struct Score(u32);
+
+// Current gravity level; rises every 10 lines cleared.
+struct Level(u32);
+
+// Total lines cleared this game, used to decide when to raise the level.
+struct LinesCleared(u32);
+
+// Upcoming tetromino types, drawn in 7-bag order (each bag is a shuffled
+// permutation of all seven pieces, so no type can repeat or drought for long).
+struct PieceBag(VecDeque<TetrominoType>);
+
+// Set once a freshly spawned tetromino overlaps the heap; halts gameplay
+// until the player restarts.
+struct GameOver(bool);
+
 // Base entity, everything is made out of blocks
 struct Block {
     color: Color,
@@ -46,6 +90,37 @@ struct MatrixPosition {
 struct Tetromino {
     tetromino_type: TetrominoType,
     index: MatrixPosition,
+    rotation: RotationState,
+}
+
+// The four SRS rotation states, named after the official guideline spawn
+// orientation and the three quarter-turns away from it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum RotationState {
+    Spawn,
+    R,
+    Two,
+    L,
+}
+
+impl RotationState {
+    fn clockwise(self) -> Self {
+        match self {
+            RotationState::Spawn => RotationState::R,
+            RotationState::R => RotationState::Two,
+            RotationState::Two => RotationState::L,
+            RotationState::L => RotationState::Spawn,
+        }
+    }
+
+    fn counter_clockwise(self) -> Self {
+        match self {
+            RotationState::Spawn => RotationState::L,
+            RotationState::L => RotationState::Two,
+            RotationState::Two => RotationState::R,
+            RotationState::R => RotationState::Spawn,
+        }
+    }
 }
 
 // A block can be part of the currently controlled tetromino.
@@ -66,13 +141,29 @@ struct NextTetromino {
 // A block can be part of the heap.
 struct Heap;
 
+// A block can be part of the translucent ghost preview of where the current
+// tetromino will land on a hard drop.
+struct GhostTetromino;
+
+// Marks the UI text entity that displays the current score.
+struct ScoreText;
+
+// Marks the UI text entity that displays the current level.
+struct LevelText;
+
+// Marks the UI text entity that displays the game-over overlay.
+struct GameOverText;
+
 impl Block {
     const SIZE: f32 = 25.0;
 }
 
 fn setup(
     mut commands: Commands,
-    mut materials: ResMut<Assets<ColorMaterial>>
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    mut bag: ResMut<PieceBag>,
+    mut next_query: Query<(Entity, &NextTetromino)>,
 ) {
     let matrix = Matrix {
         width: 10,
@@ -84,7 +175,8 @@ fn setup(
         .spawn(UiCameraComponents::default())
     ;
 
-    spawn_current_tetromino(&mut commands, &matrix, &mut materials);
+    spawn_current_tetromino(&mut commands, &matrix, &mut materials, &mut bag);
+    render_next_preview(&mut commands, &matrix, &mut materials, &bag, &mut next_query);
 
     commands
         .spawn(SpriteComponents {
@@ -96,6 +188,74 @@ fn setup(
         })
         .with(matrix)
     ;
+
+    let font = asset_server.load("assets/fonts/FiraSans-Bold.ttf").unwrap();
+
+    commands
+        .spawn(TextComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(15.0),
+                    left: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: "Score: 0".to_string(),
+                font,
+                style: TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            },
+            ..Default::default()
+        })
+        .with(ScoreText)
+        .spawn(TextComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(50.0),
+                    left: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: "Level: 1".to_string(),
+                font,
+                style: TextStyle {
+                    font_size: 30.0,
+                    color: Color::WHITE,
+                },
+            },
+            ..Default::default()
+        })
+        .with(LevelText)
+        .spawn(TextComponents {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(200.0),
+                    left: Val::Px(15.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: "".to_string(),
+                font,
+                style: TextStyle {
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            },
+            ..Default::default()
+        })
+        .with(GameOverText)
+    ;
 }
 
 fn print_info(
@@ -121,15 +281,38 @@ fn move_current_tetromino(
     mut materials: ResMut<Assets<ColorMaterial>>,
     time: Res<Time>,
     mut soft_drop_timer: ResMut<SoftDropTimer>,
+    mut score: ResMut<Score>,
+    mut level: ResMut<Level>,
+    mut lines_cleared: ResMut<LinesCleared>,
+    mut already_held: ResMut<AlreadyHeld>,
+    mut bag: ResMut<PieceBag>,
+    mut lock_timer: ResMut<LockTimer>,
+    mut game_over: ResMut<GameOver>,
     keyboard_input: Res<Input<KeyCode>>,
     mut matrix_query: Query<&Matrix>,
     mut current_query: Query<(Entity, &mut MatrixPosition, &mut Tetromino, &CurrentTetromino)>,
-    mut heap_query: Query<(&mut MatrixPosition, &Heap)>
+    mut heap_query: Query<(Entity, &mut MatrixPosition, &Heap)>,
+    mut next_query: Query<(Entity, &NextTetromino)>
 ) {
-    // Store current positions in map by entity ID
-    let mut prev_positions: HashMap<u32, (i32, i32)> = HashMap::new();
-    for (entity, position, _tetromino, _current) in &mut current_query.iter() {
-        prev_positions.insert(entity.id(), (position.x, position.y));
+    if game_over.0 {
+        return;
+    }
+
+    // The piece spawned after the previous lock may already overlap the
+    // heap if it stacked up to the top; that's a loss.
+    if !check_tetromino_positions(&mut current_query, &mut heap_query) {
+        game_over.0 = true;
+        return;
+    }
+
+    // Store current positions/rotation in map by entity ID, so a failed move
+    // or rotation can be reverted in full.
+    let mut prev_states: HashMap<u32, (i32, i32, i32, i32, RotationState)> = HashMap::new();
+    for (entity, position, tetromino, _current) in &mut current_query.iter() {
+        prev_states.insert(
+            entity.id(),
+            (position.x, position.y, tetromino.index.x, tetromino.index.y, tetromino.rotation),
+        );
     }
 
     if keyboard_input.just_pressed(KeyCode::I) || keyboard_input.just_pressed(KeyCode::Up) {
@@ -144,9 +327,23 @@ fn move_current_tetromino(
             commands.remove_one::<CurrentTetromino>(entity);
             commands.insert_one(entity, Heap);
         }
+        already_held.0 = false;
+        lock_timer.active = false;
+        lock_timer.resets = 0;
 
         for matrix in &mut matrix_query.iter() {
-            spawn_current_tetromino(&mut commands, matrix, &mut materials);
+            clear_completed_lines(
+                &mut commands,
+                matrix,
+                &mut heap_query,
+                &mut current_query,
+                &mut score,
+                &mut level,
+                &mut lines_cleared,
+                &mut soft_drop_timer,
+            );
+            spawn_current_tetromino(&mut commands, matrix, &mut materials, &mut bag);
+            render_next_preview(&mut commands, matrix, &mut materials, &bag, &mut next_query);
         }
 
         return;
@@ -185,6 +382,7 @@ fn move_current_tetromino(
 
     let mut x_over = 0;
     let mut y_over = 0;
+    let mut rotation_transition: Option<(TetrominoType, RotationState, RotationState)> = None;
 
     for (_entity, mut position, mut tetromino, _current) in &mut current_query.iter() {
         let mut move_x = move_x;
@@ -194,10 +392,19 @@ fn move_current_tetromino(
         if let Some(clockwise) = should_rotate {
             let prev_index_x = tetromino.index.x;
             let prev_index_y = tetromino.index.y;
+            let from_state = tetromino.rotation;
 
             let matrix_size = Tetromino::SIZES[tetromino.tetromino_type as usize];
             rotate_tetromino_block(&mut tetromino, matrix_size, clockwise);
 
+            let to_state = if clockwise {
+                from_state.clockwise()
+            } else {
+                from_state.counter_clockwise()
+            };
+            tetromino.rotation = to_state;
+            rotation_transition = Some((tetromino.tetromino_type, from_state, to_state));
+
             move_x += tetromino.index.x - prev_index_x;
             move_y += tetromino.index.y - prev_index_y;
         }
@@ -226,17 +433,16 @@ fn move_current_tetromino(
     if !check_tetromino_positions(&mut current_query, &mut heap_query) {
         let mut should_revert = true;
 
-        if let Some(_) = should_rotate {
-            let try_moves = [
-                ( 1,  0),
-                ( 2,  0),
-                (-1,  0),
-                (-2,  0),
-                (-1, -2), // T spins
-                ( 1, -2),
-            ];
-
-            for try_move in try_moves.iter() {
+        if let Some((tetromino_type, from_state, to_state)) = rotation_transition {
+            let kicks = wall_kick_offsets(tetromino_type, from_state, to_state);
+
+            // Offset 0 is always (0, 0), i.e. the rotation as-is, which we
+            // already tested above and know fails; start from offset 1.
+            let mut prev_kick = kicks[0];
+            for kick in kicks[1..].iter() {
+                let try_move = (kick.0 - prev_kick.0, kick.1 - prev_kick.1);
+                prev_kick = *kick;
+
                 for (_entity, mut position, _tetromino, _current) in &mut current_query.iter() {
                     position.x += try_move.0;
                     position.y += try_move.1;
@@ -247,25 +453,84 @@ fn move_current_tetromino(
                     break;
                 }
             }
-        } else {
-            // Revert movement and add to heap
+        }
+        // A failed, non-rotating move (e.g. a downward gravity/soft-drop
+        // step hitting the heap or floor) is simply reverted below rather
+        // than locking the piece immediately; see the lock-delay handling
+        // after this block.
+
+        if should_revert {
+            for (entity, mut position, mut tetromino, _current) in &mut current_query.iter() {
+                let prev_state = prev_states.get(&entity.id()).unwrap();
+                position.x = prev_state.0;
+                position.y = prev_state.1;
+                tetromino.index.x = prev_state.2;
+                tetromino.index.y = prev_state.3;
+                tetromino.rotation = prev_state.4;
+            }
+        }
+    }
+
+    // Lock delay: reset while the player keeps acting on a resting piece.
+    let resting = is_resting(&mut current_query, &mut heap_query);
+
+    let mut piece_changed = false;
+    for (entity, position, tetromino, _current) in &mut current_query.iter() {
+        if let Some(prev_state) = prev_states.get(&entity.id()) {
+            if position.x != prev_state.0 || position.y != prev_state.1 || tetromino.rotation != prev_state.4 {
+                piece_changed = true;
+            }
+        }
+    }
+
+    let player_acted = piece_changed
+        && (keyboard_input.just_pressed(KeyCode::J)
+            || keyboard_input.just_pressed(KeyCode::Left)
+            || keyboard_input.just_pressed(KeyCode::L)
+            || keyboard_input.just_pressed(KeyCode::Right)
+            || keyboard_input.just_pressed(KeyCode::X)
+            || keyboard_input.just_pressed(KeyCode::Z));
+
+    if resting {
+        if !lock_timer.active {
+            lock_timer.active = true;
+            lock_timer.resets = 0;
+            lock_timer.timer.reset();
+        } else if player_acted && lock_timer.resets < LOCK_RESET_LIMIT {
+            lock_timer.timer.reset();
+            lock_timer.resets += 1;
+        }
+
+        lock_timer.timer.tick(time.delta_seconds);
+
+        if lock_timer.timer.finished {
+            lock_timer.active = false;
+            lock_timer.resets = 0;
+
             for (entity, _position, _tetromino, _current) in &mut current_query.iter() {
                 commands.remove_one::<CurrentTetromino>(entity);
                 commands.insert_one(entity, Heap);
             }
+            already_held.0 = false;
 
             for matrix in &mut matrix_query.iter() {
-                spawn_current_tetromino(&mut commands, matrix, &mut materials);
-            }
-        }
-
-        if should_revert {
-            for (entity, mut position, _tetromino, _current) in &mut current_query.iter() {
-                let prev_position = prev_positions.get(&entity.id()).unwrap();
-                position.x = prev_position.0;
-                position.y = prev_position.1;
+                clear_completed_lines(
+                    &mut commands,
+                    matrix,
+                    &mut heap_query,
+                    &mut current_query,
+                    &mut score,
+                    &mut level,
+                    &mut lines_cleared,
+                    &mut soft_drop_timer,
+                );
+                spawn_current_tetromino(&mut commands, matrix, &mut materials, &mut bag);
+                render_next_preview(&mut commands, matrix, &mut materials, &bag, &mut next_query);
             }
         }
+    } else {
+        lock_timer.active = false;
+        lock_timer.resets = 0;
     }
 }
 
@@ -284,6 +549,142 @@ fn update_block_sprites(
     }
 }
 
+// Redraws the ghost preview at the current tetromino's hard-drop landing spot.
+fn update_ghost_tetromino(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut current_query: Query<(&MatrixPosition, &Block, &CurrentTetromino)>,
+    mut heap_query: Query<(Entity, &mut MatrixPosition, &Heap)>,
+    mut ghost_query: Query<(Entity, &GhostTetromino)>
+) {
+    for (entity, _ghost) in &mut ghost_query.iter() {
+        commands.despawn(entity);
+    }
+
+    let mut blocks: Vec<(i32, i32, Color)> = Vec::new();
+    for (position, block, _current) in &mut current_query.iter() {
+        blocks.push((position.x, position.y, block.color));
+    }
+
+    if blocks.is_empty() {
+        return;
+    }
+
+    let mut drop = 0;
+    loop {
+        let candidate: Vec<(i32, i32)> = blocks.iter()
+            .map(|(x, y, _color)| (*x, *y - drop - 1))
+            .collect();
+
+        if !positions_valid(&candidate, &mut heap_query) {
+            break;
+        }
+
+        drop += 1;
+    }
+
+    for (x, y, color) in blocks {
+        commands
+            .spawn(SpriteComponents {
+                material: materials.add(Color::rgba(color.r, color.g, color.b, 0.25).into()),
+                sprite: Sprite {
+                    size: Vec2::new(Block::SIZE, Block::SIZE),
+                },
+                translation: Translation(Vec3::new(0.0, 0.0, 0.5)),
+                ..Default::default()
+            })
+            .with(GhostTetromino)
+            .with(MatrixPosition { x, y: y - drop })
+        ;
+    }
+}
+
+fn update_score_ui(
+    score: Res<Score>,
+    level: Res<Level>,
+    mut score_query: Query<(&ScoreText, &mut Text)>,
+    mut level_query: Query<(&LevelText, &mut Text)>
+) {
+    for (_score_text, mut text) in &mut score_query.iter() {
+        text.value = format!("Score: {}", score.0);
+    }
+
+    for (_level_text, mut text) in &mut level_query.iter() {
+        text.value = format!("Level: {}", level.0);
+    }
+}
+
+fn update_game_over_ui(
+    game_over: Res<GameOver>,
+    score: Res<Score>,
+    mut text_query: Query<(&GameOverText, &mut Text)>
+) {
+    for (_game_over_text, mut text) in &mut text_query.iter() {
+        text.value = if game_over.0 {
+            format!("Game Over\nFinal score: {}\nPress R to restart", score.0)
+        } else {
+            "".to_string()
+        };
+    }
+}
+
+// Clears the board and all game state back to a fresh game, as long as the
+// player is looking at the game-over overlay and presses `R`.
+fn restart_game(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut game_over: ResMut<GameOver>,
+    mut score: ResMut<Score>,
+    mut level: ResMut<Level>,
+    mut lines_cleared: ResMut<LinesCleared>,
+    mut already_held: ResMut<AlreadyHeld>,
+    mut lock_timer: ResMut<LockTimer>,
+    mut soft_drop_timer: ResMut<SoftDropTimer>,
+    mut bag: ResMut<PieceBag>,
+    mut matrix_query: Query<&Matrix>,
+    mut heap_query: Query<(Entity, &Heap)>,
+    mut current_query: Query<(Entity, &CurrentTetromino)>,
+    mut ghost_query: Query<(Entity, &GhostTetromino)>,
+    mut held_query: Query<(Entity, &HeldTetromino)>,
+    mut next_query: Query<(Entity, &NextTetromino)>
+) {
+    if !game_over.0 || !keyboard_input.just_pressed(KeyCode::R) {
+        return;
+    }
+
+    for (entity, _heap) in &mut heap_query.iter() {
+        commands.despawn(entity);
+    }
+
+    for (entity, _current) in &mut current_query.iter() {
+        commands.despawn(entity);
+    }
+
+    for (entity, _ghost) in &mut ghost_query.iter() {
+        commands.despawn(entity);
+    }
+
+    for (entity, _held) in &mut held_query.iter() {
+        commands.despawn(entity);
+    }
+
+    score.0 = 0;
+    level.0 = 1;
+    lines_cleared.0 = 0;
+    already_held.0 = false;
+    lock_timer.active = false;
+    lock_timer.resets = 0;
+    soft_drop_timer.0 = Timer::from_seconds(soft_drop_interval(level.0));
+    bag.0.clear();
+    game_over.0 = false;
+
+    for matrix in &mut matrix_query.iter() {
+        spawn_current_tetromino(&mut commands, matrix, &mut materials, &mut bag);
+        render_next_preview(&mut commands, matrix, &mut materials, &bag, &mut next_query);
+    }
+}
+
 // ----------------
 // UTILITY AND IMPL
 // ----------------
@@ -303,16 +704,70 @@ fn rotate_tetromino_block(tetromino_block: &mut Tetromino, matrix_size: i32, clo
     }
 }
 
+// SRS wall-kick offsets for J, L, S, T and Z, indexed by rotation transition
+// (see `srs_transition_index`). Each row is tried in order until one fits.
+const JLSTZ_WALL_KICKS: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 0 -> R
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R -> 0
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],     // R -> 2
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)], // 2 -> R
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 2 -> L
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L -> 2
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],  // L -> 0
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],    // 0 -> L
+];
+
+// SRS wall-kick offsets for the I piece, which kicks differently from the
+// other pieces because its pivot isn't centered in its bounding box.
+const I_WALL_KICKS: [[(i32, i32); 5]; 8] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],  // 0 -> R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],  // R -> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],  // R -> 2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],  // 2 -> R
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],  // 2 -> L
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],  // L -> 2
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],  // L -> 0
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],  // 0 -> L
+];
+
+fn srs_transition_index(from: RotationState, to: RotationState) -> usize {
+    match (from, to) {
+        (RotationState::Spawn, RotationState::R) => 0,
+        (RotationState::R, RotationState::Spawn) => 1,
+        (RotationState::R, RotationState::Two) => 2,
+        (RotationState::Two, RotationState::R) => 3,
+        (RotationState::Two, RotationState::L) => 4,
+        (RotationState::L, RotationState::Two) => 5,
+        (RotationState::L, RotationState::Spawn) => 6,
+        (RotationState::Spawn, RotationState::L) => 7,
+        _ => unreachable!("rotation only ever steps one quarter turn"),
+    }
+}
+
+// Looks up the ordered list of offsets to try for a rotation between two SRS
+// states. The O piece has no meaningful rotation, so it never kicks.
+fn wall_kick_offsets(tetromino_type: TetrominoType, from: RotationState, to: RotationState) -> [(i32, i32); 5] {
+    if let TetrominoType::O = tetromino_type {
+        return [(0, 0); 5];
+    }
+
+    let index = srs_transition_index(from, to);
+    match tetromino_type {
+        TetrominoType::I => I_WALL_KICKS[index],
+        _ => JLSTZ_WALL_KICKS[index],
+    }
+}
+
 fn check_tetromino_positions(
     current_query: &mut Query<(Entity, &mut MatrixPosition, &mut Tetromino, &CurrentTetromino)>,
-    heap_query: &mut Query<(&mut MatrixPosition, &Heap)>
+    heap_query: &mut Query<(Entity, &mut MatrixPosition, &Heap)>
 ) -> bool {
     for (_entity, position, _tetromino, _current) in &mut current_query.iter() {
         if position.y < 0 {
             return false;
         }
 
-        for (heap_position, _heap) in &mut heap_query.iter() {
+        for (_heap_entity, heap_position, _heap) in &mut heap_query.iter() {
             if position.x == heap_position.x && position.y == heap_position.y {
                 return false;
             }
@@ -322,12 +777,205 @@ fn check_tetromino_positions(
     return true;
 }
 
+// Tests whether the current tetromino would overlap the heap/floor if it
+// moved down one more row, without leaving it there.
+fn is_resting(
+    current_query: &mut Query<(Entity, &mut MatrixPosition, &mut Tetromino, &CurrentTetromino)>,
+    heap_query: &mut Query<(Entity, &mut MatrixPosition, &Heap)>
+) -> bool {
+    for (_entity, mut position, _tetromino, _current) in &mut current_query.iter() {
+        position.y -= 1;
+    }
+
+    let blocked = !check_tetromino_positions(current_query, heap_query);
+
+    for (_entity, mut position, _tetromino, _current) in &mut current_query.iter() {
+        position.y += 1;
+    }
+
+    blocked
+}
+
+// Same occupancy test as `check_tetromino_positions`, but against plain
+// cloned coordinates instead of a live query.
+fn positions_valid(
+    positions: &[(i32, i32)],
+    heap_query: &mut Query<(Entity, &mut MatrixPosition, &Heap)>
+) -> bool {
+    for &(x, y) in positions {
+        if y < 0 {
+            return false;
+        }
+
+        for (_heap_entity, heap_position, _heap) in &mut heap_query.iter() {
+            if x == heap_position.x && y == heap_position.y {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+// Scans for and clears fully-filled rows, then awards score/level. Also
+// scans `locking_query`, not yet visible in `heap_query` until Commands flush.
+fn clear_completed_lines(
+    commands: &mut Commands,
+    matrix: &Matrix,
+    heap_query: &mut Query<(Entity, &mut MatrixPosition, &Heap)>,
+    locking_query: &mut Query<(Entity, &mut MatrixPosition, &mut Tetromino, &CurrentTetromino)>,
+    score: &mut ResMut<Score>,
+    level: &mut ResMut<Level>,
+    lines_cleared: &mut ResMut<LinesCleared>,
+    soft_drop_timer: &mut ResMut<SoftDropTimer>,
+) {
+    let mut cleared_rows = Vec::new();
+
+    for y in 0..matrix.height {
+        let mut filled = 0;
+        for (_entity, position, _heap) in &mut heap_query.iter() {
+            if position.y == y {
+                filled += 1;
+            }
+        }
+        for (_entity, position, _tetromino, _current) in &mut locking_query.iter() {
+            if position.y == y {
+                filled += 1;
+            }
+        }
+
+        if filled == matrix.width {
+            cleared_rows.push(y);
+        }
+    }
+
+    if cleared_rows.is_empty() {
+        return;
+    }
+
+    for (entity, mut position, _heap) in &mut heap_query.iter() {
+        if cleared_rows.contains(&position.y) {
+            commands.despawn(entity);
+        } else {
+            let shift = cleared_rows.iter().filter(|&&row| row < position.y).count() as i32;
+            position.y -= shift;
+        }
+    }
+
+    for (entity, mut position, _tetromino, _current) in &mut locking_query.iter() {
+        if cleared_rows.contains(&position.y) {
+            commands.despawn(entity);
+        } else {
+            let shift = cleared_rows.iter().filter(|&&row| row < position.y).count() as i32;
+            position.y -= shift;
+        }
+    }
+
+    let lines = cleared_rows.len() as u32;
+    let base_points = match lines {
+        1 => 100,
+        2 => 300,
+        3 => 500,
+        _ => 800,
+    };
+    score.0 += base_points * level.0;
+    lines_cleared.0 += lines;
+
+    let new_level = 1 + lines_cleared.0 / 10;
+    if new_level > level.0 {
+        level.0 = new_level;
+        soft_drop_timer.0 = Timer::from_seconds(soft_drop_interval(level.0));
+    }
+}
+
+// Gravity speeds up as the level rises; floors out at 0.1s so high levels
+// stay playable.
+fn soft_drop_interval(level: u32) -> f32 {
+    (0.750 - (level.saturating_sub(1) as f32 * 0.05)).max(0.1)
+}
+
+// Appends whole shuffled 7-bags while fewer than seven pieces are queued, so
+// at least seven upcoming pieces are always known before a draw.
+fn refill_bag(bag: &mut VecDeque<TetrominoType>) {
+    while bag.len() < 7 {
+        let mut types = [
+            TetrominoType::I,
+            TetrominoType::O,
+            TetrominoType::T,
+            TetrominoType::S,
+            TetrominoType::Z,
+            TetrominoType::L,
+            TetrominoType::J,
+        ];
+        types.shuffle(&mut rand::thread_rng());
+        bag.extend(types.iter().copied());
+    }
+}
+
+// How many upcoming pieces are shown in the next-piece queue.
+const PREVIEW_COUNT: usize = 5;
+
+// Redraws the next-piece preview from the front of the bag.
+fn render_next_preview(
+    commands: &mut Commands,
+    matrix: &Matrix,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    bag: &ResMut<PieceBag>,
+    next_query: &mut Query<(Entity, &NextTetromino)>,
+) {
+    for (entity, _next) in &mut next_query.iter() {
+        commands.despawn(entity);
+    }
+
+    for (slot, tetromino_type) in bag.0.iter().take(PREVIEW_COUNT).enumerate() {
+        let blocks = Tetromino::blocks_from_type(*tetromino_type);
+        let tetromino_matrix_size = Tetromino::SIZES[*tetromino_type as usize];
+
+        for block in blocks.into_iter() {
+            commands
+                .spawn(SpriteComponents {
+                    material: materials.add(Color::rgb(
+                        block.0.color.r,
+                        block.0.color.g,
+                        block.0.color.b
+                    ).into()),
+                    sprite: Sprite {
+                        size: Vec2::new(Block::SIZE, Block::SIZE),
+                    },
+                    translation: Translation(Vec3::new(0.0, 0.0, 1.0)),
+                    ..Default::default()
+                })
+                .with(NextTetromino { pos_in_line: slot as u8 })
+                .with(MatrixPosition {
+                    x: matrix.width + 2 + block.1.index.x,
+                    y: matrix.height - 3 - (slot as i32 * 4) - (4 - tetromino_matrix_size) + block.1.index.y,
+                })
+                .with_bundle(block)
+            ;
+        }
+    }
+}
+
 fn spawn_current_tetromino(
     commands: &mut Commands,
     matrix: &Matrix,
     materials: &mut ResMut<Assets<ColorMaterial>>,
+    bag: &mut ResMut<PieceBag>,
+) {
+    refill_bag(&mut bag.0);
+    let tetromino_type = bag.0.pop_front().unwrap();
+
+    spawn_tetromino_at_spawn(commands, matrix, materials, tetromino_type);
+}
+
+// Spawns a fresh `CurrentTetromino` of the given type at the top of the matrix.
+fn spawn_tetromino_at_spawn(
+    commands: &mut Commands,
+    matrix: &Matrix,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    tetromino_type: TetrominoType,
 ) {
-    let blocks = Tetromino::blocks_from_type(rand::random());
+    let blocks = Tetromino::blocks_from_type(tetromino_type);
     for block in blocks.into_iter() {
         let tetromino_matrix_size = Tetromino::SIZES[block.1.tetromino_type as usize];
         commands
@@ -353,6 +1001,89 @@ fn spawn_current_tetromino(
     }
 }
 
+// Renders the held piece in its preview slot to the left of the matrix.
+// Callers despawn the previous held blocks before calling this.
+fn render_held_preview(
+    commands: &mut Commands,
+    matrix: &Matrix,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    tetromino_type: TetrominoType,
+) {
+    let blocks = Tetromino::blocks_from_type(tetromino_type);
+    let tetromino_matrix_size = Tetromino::SIZES[tetromino_type as usize];
+
+    for block in blocks.into_iter() {
+        commands
+            .spawn(SpriteComponents {
+                material: materials.add(Color::rgb(
+                    block.0.color.r,
+                    block.0.color.g,
+                    block.0.color.b
+                ).into()),
+                sprite: Sprite {
+                    size: Vec2::new(Block::SIZE, Block::SIZE),
+                },
+                translation: Translation(Vec3::new(0.0, 0.0, 1.0)),
+                ..Default::default()
+            })
+            .with(HeldTetromino)
+            .with(MatrixPosition {
+                x: -4 + block.1.index.x,
+                y: matrix.height - tetromino_matrix_size + block.1.index.y,
+            })
+            .with_bundle(block)
+        ;
+    }
+}
+
+// Swaps the current piece into the hold slot (`C`), once per piece lock.
+fn hold_current_tetromino(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    keyboard_input: Res<Input<KeyCode>>,
+    game_over: Res<GameOver>,
+    mut already_held: ResMut<AlreadyHeld>,
+    mut bag: ResMut<PieceBag>,
+    mut matrix_query: Query<&Matrix>,
+    mut current_query: Query<(Entity, &Tetromino, &CurrentTetromino)>,
+    mut held_query: Query<(Entity, &Tetromino, &HeldTetromino)>,
+    mut next_query: Query<(Entity, &NextTetromino)>
+) {
+    if game_over.0 || !keyboard_input.just_pressed(KeyCode::C) || already_held.0 {
+        return;
+    }
+
+    let mut current_type = None;
+    for (entity, tetromino, _current) in &mut current_query.iter() {
+        current_type = Some(tetromino.tetromino_type);
+        commands.despawn(entity);
+    }
+    let current_type = match current_type {
+        Some(current_type) => current_type,
+        None => return,
+    };
+
+    let mut held_type = None;
+    for (entity, tetromino, _held) in &mut held_query.iter() {
+        held_type = Some(tetromino.tetromino_type);
+        commands.despawn(entity);
+    }
+
+    for matrix in &mut matrix_query.iter() {
+        render_held_preview(&mut commands, matrix, &mut materials, current_type);
+
+        match held_type {
+            Some(held_type) => spawn_tetromino_at_spawn(&mut commands, matrix, &mut materials, held_type),
+            None => {
+                spawn_current_tetromino(&mut commands, matrix, &mut materials, &mut bag);
+                render_next_preview(&mut commands, matrix, &mut materials, &bag, &mut next_query);
+            }
+        }
+    }
+
+    already_held.0 = true;
+}
+
 #[derive(Copy, Clone, Debug)]
 enum TetrominoType {
     I = 0,
@@ -445,7 +1176,13 @@ impl Tetromino {
                             x: index.0,
                             y: index.1,
                         },
-                        tetromino_type
+                        tetromino_type,
+                        // The I piece starts as the vertical column x = 1,
+                        // which is the guideline L orientation.
+                        rotation: match tetromino_type {
+                            TetrominoType::I => RotationState::L,
+                            _ => RotationState::Spawn,
+                        },
                     }
                 )
             })